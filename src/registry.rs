@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use futures::{Future, BoxFuture};
+use futures::future::{join_all, ok};
+use super::*;
+
+// A collection of named `Room`s, letting a server host many concurrent
+// games/channels instead of a single lobby.
+#[derive(Clone)]
+pub struct RoomRegistry<N, T, R>
+    where N: Clone + Hash + Eq,
+          T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    rooms: HashMap<N, Room<T, R>>,
+}
+
+impl<N, T, R> RoomRegistry<N, T, R>
+    where N: Clone + Hash + Eq,
+          T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    pub fn new() -> RoomRegistry<N, T, R> {
+        RoomRegistry { rooms: HashMap::new() }
+    }
+
+    // Creates an empty room under `name`. Returns `false` (leaving the
+    // existing room untouched) if `name` is already taken.
+    pub fn create(&mut self, name: N) -> bool {
+        if self.rooms.contains_key(&name) {
+            return false;
+        }
+        self.rooms.insert(name, Room::default());
+        true
+    }
+
+    pub fn get(&self, name: &N) -> Option<&Room<T, R>> {
+        self.rooms.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &N) -> Option<&mut Room<T, R>> {
+        self.rooms.get_mut(name)
+    }
+
+    pub fn remove(&mut self, name: &N) -> Option<Room<T, R>> {
+        self.rooms.remove(name)
+    }
+
+    pub fn room_names(&self) -> Vec<N> {
+        self.rooms.keys().cloned().collect()
+    }
+
+    // A client can be joined to several rooms at once; this lists them all.
+    pub fn rooms_of(&self, id: &ClientId) -> Vec<N> {
+        self.rooms
+            .iter()
+            .filter(|&(_, room)| room.contains(id))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    // Relocates a client from `from` to `to`, using the existing
+    // `Room::remove`/`Room::insert` pair so the move is atomic from the
+    // registry's point of view. Returns `false`, leaving `from` untouched,
+    // if `from` has no such client or `to` already has a collision.
+    pub fn move_client(&mut self, id: &ClientId, from: &N, to: &N) -> bool {
+        // `from == to` is a no-op move: checking the destination for a
+        // collision before removing would always see the client's own
+        // membership there and wrongly report a collision.
+        if from == to {
+            return self.rooms.get(from).map_or(false, |room| room.contains(id));
+        }
+        if self.rooms.get(to).map_or(true, |room| room.contains(id)) {
+            return false;
+        }
+        match self.rooms.get_mut(from).and_then(|room| room.remove(id)) {
+            Some(client) => {
+                self.rooms.get_mut(to).unwrap().insert(client);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn broadcast_to(&mut self,
+                         name: &N,
+                         msg: T)
+                         -> BoxFuture<<Room<T, R> as Communicator>::Status, ()> {
+        match self.rooms.get_mut(name) {
+            Some(room) => room.broadcast(msg),
+            None => ok(HashMap::new()).boxed(),
+        }
+    }
+
+    pub fn broadcast_all(&mut self, msg: T) -> BoxFuture<Vec<<Room<T, R> as Communicator>::Status>, ()> {
+        let broadcasts = self.rooms
+            .values_mut()
+            .map(|room| room.broadcast(msg.clone()))
+            .collect::<Vec<_>>();
+        join_all(broadcasts).boxed()
+    }
+}
+
+impl<N, T, R> Default for RoomRegistry<N, T, R>
+    where N: Clone + Hash + Eq,
+          T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    fn default() -> RoomRegistry<N, T, R> {
+        RoomRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test::*;
+
+    #[test]
+    fn move_client_relocates_between_rooms() {
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let mut registry: RoomRegistry<&str, TinyMsg, TinyMsg> = RoomRegistry::new();
+        registry.create("a");
+        registry.create("b");
+        registry.get_mut(&"a").unwrap().insert(client);
+
+        assert!(registry.move_client(&id, &"a", &"b"));
+        assert!(!registry.get(&"a").unwrap().contains(&id));
+        assert!(registry.get(&"b").unwrap().contains(&id));
+    }
+
+    #[test]
+    fn move_client_refuses_to_clobber_a_collision() {
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let mut registry: RoomRegistry<&str, TinyMsg, TinyMsg> = RoomRegistry::new();
+        registry.create("a");
+        registry.create("b");
+        // The same client joined to both rooms at once, which the registry
+        // explicitly allows.
+        registry.get_mut(&"a").unwrap().insert(client.clone());
+        registry.get_mut(&"b").unwrap().insert(client);
+
+        assert!(!registry.move_client(&id, &"a", &"b"));
+        assert!(registry.get(&"a").unwrap().contains(&id));
+        assert!(registry.get(&"b").unwrap().contains(&id));
+    }
+
+    #[test]
+    fn move_client_to_its_own_room_is_a_no_op_success() {
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let mut registry: RoomRegistry<&str, TinyMsg, TinyMsg> = RoomRegistry::new();
+        registry.create("a");
+        registry.get_mut(&"a").unwrap().insert(client);
+
+        assert!(registry.move_client(&id, &"a", &"a"));
+        assert!(registry.get(&"a").unwrap().contains(&id));
+    }
+}
@@ -1,7 +1,18 @@
 use super::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use futures::{Future, BoxFuture};
-use futures::future::{join_all, JoinAll};
+use futures::future::{join_all, ok, JoinAll};
+
+mod broadcast;
+mod keepalive;
+mod bridge;
+mod handle;
+
+pub use self::broadcast::Broadcast;
+pub use self::keepalive::Keepalive;
+pub use self::bridge::Bridge;
+pub use self::handle::RoomHandle;
 
 #[derive(Clone)]
 pub struct Room<T, R>
@@ -9,6 +20,8 @@ pub struct Room<T, R>
           R: Send + 'static
 {
     clients: HashMap<ClientId, Client<T, R>>,
+    topic: Option<String>,
+    attrs: HashMap<String, String>,
 }
 
 impl<T, R> Room<T, R>
@@ -17,7 +30,11 @@ impl<T, R> Room<T, R>
 {
     pub fn new(clients: Vec<Client<T, R>>) -> Room<T, R> {
         let clients = clients.into_iter().map(|c| (c.id(), c)).collect();
-        Room { clients: clients }
+        Room {
+            clients: clients,
+            topic: None,
+            attrs: HashMap::new(),
+        }
     }
 
     pub fn client_ids(&self) -> Vec<ClientId> {
@@ -82,6 +99,93 @@ impl<T, R> Room<T, R>
             .map(|results| results.into_iter().collect())
             .boxed()
     }
+
+    // Consumes the room and returns a `Stream` that, every `interval`,
+    // transmits `ping_msg` to every client and prunes any whose transport
+    // has died (a failed send, or a `ClientStatus` of `Closed`).
+    pub fn keepalive(self, interval: Duration, ping_msg: T) -> Keepalive<T, R>
+        where T: Clone
+    {
+        Keepalive::new(self, interval, ping_msg)
+    }
+
+    // Relays traffic between this room and `other`, whose payload types may
+    // differ. `to_other` maps a message received here into one broadcast to
+    // `other`; `to_self` maps the reverse. Returning `None` from either
+    // drops that message instead of forwarding it. See `Bridge` for how the
+    // stream this returns is driven for as long as the bridge should run.
+    pub fn bridge<T2, R2>(self,
+                           other: Room<T2, R2>,
+                           timeout: ClientTimeout,
+                           to_other: Box<Fn(R) -> Option<T2> + Send + Sync>,
+                           to_self: Box<Fn(R2) -> Option<T> + Send + Sync>)
+                           -> Bridge<T, R, T2, R2>
+        where T2: Clone + Send + 'static,
+              R2: Send + 'static
+    {
+        Bridge::new(self, other, timeout, to_other, to_self)
+    }
+
+    // Moves this room into a spawned task and returns a cloneable
+    // `RoomHandle` onto it, for sharing across connection-handling tasks
+    // that each need to push into the same room concurrently.
+    pub fn into_handle(self) -> RoomHandle<T, R>
+        where T: Clone
+    {
+        RoomHandle::spawn(self)
+    }
+
+    // Broadcasts to every client except `exclude`, so a message is never
+    // echoed back to clients that already know about it (e.g. its sender).
+    pub fn broadcast_except(self, msg: T, exclude: &[ClientId]) -> Broadcast<T, R>
+        where T: Clone
+    {
+        let exclude: HashSet<ClientId> = exclude.iter().cloned().collect();
+        let ids = self.clients.keys().filter(|id| !exclude.contains(id)).cloned().collect();
+        Broadcast::new(self, msg, ids)
+    }
+
+    // Relays `msg` to every client other than `from`, the common "someone
+    // sent this, tell everyone else" case.
+    pub fn relay(self, from: ClientId, msg: T) -> Broadcast<T, R>
+        where T: Clone
+    {
+        self.broadcast_except(msg, &[from])
+    }
+
+    pub fn topic(&self) -> Option<&str> {
+        self.topic.as_ref().map(String::as_str)
+    }
+
+    pub fn set_topic(&mut self, topic: Option<String>) {
+        self.topic = topic;
+    }
+
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs.get(key).map(String::as_str)
+    }
+
+    pub fn set_attr(&mut self, key: String, value: String) -> Option<String> {
+        self.attrs.insert(key, value)
+    }
+
+    // Sets the topic and, mirroring the lavina `change_topic` command,
+    // optionally fans `notify` out to every current member over the
+    // existing broadcast machinery, giving late joiners (via a registry's
+    // join path) a standard place to learn current room state instead of a
+    // side-map keyed by room.
+    pub fn change_topic(&mut self,
+                         topic: Option<String>,
+                         notify: Option<T>)
+                         -> BoxFuture<<Self as Communicator>::Status, ()>
+        where T: Clone
+    {
+        self.set_topic(topic);
+        match notify {
+            Some(msg) => self.broadcast(msg),
+            None => ok(HashMap::new()).boxed(),
+        }
+    }
 }
 
 impl<T, R> Default for Room<T, R>
@@ -89,7 +193,11 @@ impl<T, R> Default for Room<T, R>
           R: Send + 'static
 {
     fn default() -> Room<T, R> {
-        Room { clients: HashMap::new() }
+        Room {
+            clients: HashMap::new(),
+            topic: None,
+            attrs: HashMap::new(),
+        }
     }
 }
 
@@ -159,7 +267,7 @@ impl<T, R> Broadcasting for (Room<T, R>, Room<T, R>)
 mod tests {
     use super::*;
     use super::test::*;
-    use futures::Stream;
+    use futures::{Async, Stream};
 
     #[test]
     fn can_transmit() {
@@ -182,4 +290,57 @@ mod tests {
             _ => assert!(false),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn broadcast_except_excludes_given_client() {
+        let (mut rx0, client0) = mock_client_channelled();
+        let client0_id = client0.id();
+
+        let (rx1, client1) = mock_client_channelled();
+        let mut client1_rx = rx1.wait().peekable();
+        let client1_id = client1.id();
+
+        let room = Room::new(vec![client0, client1]);
+
+        let (room, failed) = room.broadcast_except(TinyMsg::A, &[client0_id]).wait().unwrap();
+        assert!(failed.is_empty());
+        assert!(room.contains(&client1_id));
+
+        match rx0.poll() {
+            Ok(Async::NotReady) => {}
+            _ => assert!(false),
+        }
+        match client1_rx.next() {
+            Some(Ok(_)) => {}
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn topic_and_attrs_round_trip() {
+        let mut room: Room<TinyMsg, TinyMsg> = Room::default();
+        assert_eq!(room.topic(), None);
+        assert_eq!(room.attr("colour"), None);
+
+        room.set_topic(Some("general chat".to_string()));
+        room.set_attr("colour".to_string(), "red".to_string());
+
+        assert_eq!(room.topic(), Some("general chat"));
+        assert_eq!(room.attr("colour"), Some("red"));
+    }
+
+    #[test]
+    fn change_topic_notifies_members() {
+        let (rx0, client0) = mock_client_channelled();
+        let mut client0_rx = rx0.wait().peekable();
+
+        let mut room = Room::new(vec![client0]);
+        room.change_topic(Some("general chat".to_string()), Some(TinyMsg::A)).wait().unwrap();
+
+        assert_eq!(room.topic(), Some("general chat"));
+        match client0_rx.next() {
+            Some(Ok(_)) => {}
+            _ => assert!(false),
+        }
+    }
+}
@@ -1,45 +1,45 @@
-use std::hash::Hash;
 use std::collections::HashSet;
-use futures::{Future, Sink, Stream, Poll, Async, AsyncSink};
+use futures::{Future, Sink, Poll, Async, AsyncSink};
 use super::*;
 
-pub struct Broadcast<I, C>
-    where I: Clone + Send + PartialEq + Eq + Hash + Debug + 'static,
-          C: Sink + Stream + 'static,
-          C::SinkItem: Clone
+pub struct Broadcast<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
 {
-    room: Option<Room<I, C>>,
-    msg: C::SinkItem,
-    start_send_list: HashSet<I>,
-    poll_complete_list: Vec<I>,
+    room: Option<Room<T, R>>,
+    msg: T,
+    start_send_list: HashSet<ClientId>,
+    poll_complete_list: Vec<ClientId>,
+    failed: HashSet<ClientId>,
 }
 
-impl<I, C> Broadcast<I, C>
-    where I: Clone + Send + PartialEq + Eq + Hash + Debug + 'static,
-          C: Sink + Stream + 'static,
-          C::SinkItem: Clone
+impl<T, R> Broadcast<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
 {
     #[doc(hidden)]
-    pub fn new(room: Room<I, C>, msg: C::SinkItem, ids: HashSet<I>) -> Broadcast<I, C> {
+    pub fn new(room: Room<T, R>, msg: T, ids: HashSet<ClientId>) -> Broadcast<T, R> {
         Broadcast {
             room: Some(room),
             msg: msg,
             start_send_list: ids,
             poll_complete_list: vec![],
+            failed: HashSet::new(),
         }
     }
 
-    pub fn into_inner(mut self) -> Room<I, C> {
+    pub fn into_inner(mut self) -> Room<T, R> {
         self.room.take().unwrap()
     }
 }
 
-impl<I, C> Future for Broadcast<I, C>
-    where I: Clone + Send + PartialEq + Eq + Hash + Debug + 'static,
-          C: Sink + Stream + 'static,
-          C::SinkItem: Clone
+impl<T, R> Future for Broadcast<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
 {
-    type Item = Room<I, C>;
+    // The room, handed back once every send has resolved, paired with the
+    // ids of clients whose `start_send`/`poll_complete` returned `Err`.
+    type Item = (Room<T, R>, HashSet<ClientId>);
     type Error = ();
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
@@ -58,7 +58,9 @@ impl<I, C> Future for Broadcast<I, C>
                 Ok(AsyncSink::Ready) => {
                     self.poll_complete_list.push(id);
                 }
-                Err(_) => {}
+                Err(_) => {
+                    self.failed.insert(id);
+                }
             }
         }
 
@@ -72,12 +74,15 @@ impl<I, C> Future for Broadcast<I, C>
                 Ok(Async::NotReady) => {
                     self.poll_complete_list.push(id);
                 }
-                Ok(Async::Ready(())) | Err(_) => {}
+                Ok(Async::Ready(())) => {}
+                Err(_) => {
+                    self.failed.insert(id);
+                }
             }
         }
 
         if self.start_send_list.is_empty() && self.poll_complete_list.is_empty() {
-            Ok(Async::Ready(room))
+            Ok(Async::Ready((room, self.failed.drain().collect())))
         } else {
             self.room = Some(room);
             Ok(Async::NotReady)
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use futures::{Future, Stream, BoxFuture, Poll, Async};
+use futures::future::{ok, err};
+use futures::sync::{mpsc, oneshot};
+use super::*;
+
+// The operations a `RoomHandle` can ask the owning task to perform, each
+// carrying a oneshot reply sender so the caller can `await` the result.
+enum RoomCommand<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    Transmit(HashMap<ClientId, T>, oneshot::Sender<HashMap<ClientId, ClientStatus>>),
+    Broadcast(T, oneshot::Sender<HashMap<ClientId, ClientStatus>>),
+    Receive(ClientTimeout,
+            oneshot::Sender<(HashMap<ClientId, ClientStatus>, HashMap<ClientId, R>)>),
+    Join(Client<T, R>, oneshot::Sender<bool>),
+    Leave(ClientId, oneshot::Sender<Option<Client<T, R>>>),
+    Status(oneshot::Sender<HashMap<ClientId, ClientStatus>>),
+    Close(oneshot::Sender<HashMap<ClientId, ClientStatus>>),
+}
+
+// Owns a `Room` inside a spawned task and applies commands to it serially,
+// one at a time, which is what keeps every `&mut self` `Room` method sound
+// without needing a lock.
+struct RoomTask<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    room: Room<T, R>,
+    commands: mpsc::UnboundedReceiver<RoomCommand<T, R>>,
+    in_flight: Option<BoxFuture<(), ()>>,
+    closing: bool,
+}
+
+impl<T, R> RoomTask<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    fn apply(&mut self, cmd: RoomCommand<T, R>) -> BoxFuture<(), ()> {
+        match cmd {
+            RoomCommand::Transmit(msgs, reply) => {
+                self.room.transmit(msgs).map(|status| { let _ = reply.send(status); }).boxed()
+            }
+            RoomCommand::Broadcast(msg, reply) => {
+                self.room.broadcast(msg).map(|status| { let _ = reply.send(status); }).boxed()
+            }
+            RoomCommand::Receive(timeout, reply) => {
+                self.room.receive(timeout).map(|result| { let _ = reply.send(result); }).boxed()
+            }
+            RoomCommand::Join(client, reply) => {
+                let joined = self.room.insert(client);
+                let _ = reply.send(joined);
+                ok(()).boxed()
+            }
+            RoomCommand::Leave(id, reply) => {
+                let client = self.room.remove(&id);
+                let _ = reply.send(client);
+                ok(()).boxed()
+            }
+            RoomCommand::Status(reply) => {
+                self.room.status().map(|status| { let _ = reply.send(status); }).boxed()
+            }
+            RoomCommand::Close(reply) => {
+                self.closing = true;
+                self.room.close().map(|status| { let _ = reply.send(status); }).boxed()
+            }
+        }
+    }
+}
+
+impl<T, R> Future for RoomTask<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            if let Some(mut in_flight) = self.in_flight.take() {
+                match in_flight.poll() {
+                    Ok(Async::Ready(())) => {}
+                    Ok(Async::NotReady) => {
+                        self.in_flight = Some(in_flight);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(()) => {}
+                }
+                if self.closing {
+                    return Ok(Async::Ready(()));
+                }
+            }
+
+            match self.commands.poll() {
+                Ok(Async::Ready(Some(cmd))) => {
+                    self.in_flight = Some(self.apply(cmd));
+                }
+                Ok(Async::Ready(None)) => return Ok(Async::Ready(())),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(()) => return Err(()),
+            }
+        }
+    }
+}
+
+// A cloneable, `Send` handle onto a `Room` running in a spawned task. Every
+// call goes over an mpsc command channel and is answered on a oneshot, so
+// many connection-handling tasks can share one room without locking.
+pub struct RoomHandle<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    commands: mpsc::UnboundedSender<RoomCommand<T, R>>,
+}
+
+// Written by hand rather than derived, so cloning a handle does not require
+// `R: Clone`: only the command sender is cloned.
+impl<T, R> Clone for RoomHandle<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    fn clone(&self) -> RoomHandle<T, R> {
+        RoomHandle { commands: self.commands.clone() }
+    }
+}
+
+impl<T, R> RoomHandle<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    pub fn spawn(room: Room<T, R>) -> RoomHandle<T, R> {
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+        ::tokio::spawn(task);
+        RoomHandle { commands: commands_tx }
+    }
+
+    fn call<F, I>(&self, make_cmd: F) -> BoxFuture<I, ()>
+        where F: FnOnce(oneshot::Sender<I>) -> RoomCommand<T, R>,
+              I: Send + 'static
+    {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        match self.commands.unbounded_send(make_cmd(reply_tx)) {
+            Ok(()) => reply_rx.map_err(|_| ()).boxed(),
+            Err(_) => err(()).boxed(),
+        }
+    }
+
+    pub fn transmit(&self, msgs: HashMap<ClientId, T>) -> BoxFuture<HashMap<ClientId, ClientStatus>, ()> {
+        self.call(|reply| RoomCommand::Transmit(msgs, reply))
+    }
+
+    pub fn broadcast(&self, msg: T) -> BoxFuture<HashMap<ClientId, ClientStatus>, ()> {
+        self.call(|reply| RoomCommand::Broadcast(msg, reply))
+    }
+
+    pub fn receive(&self,
+                    timeout: ClientTimeout)
+                    -> BoxFuture<(HashMap<ClientId, ClientStatus>, HashMap<ClientId, R>), ()> {
+        self.call(|reply| RoomCommand::Receive(timeout, reply))
+    }
+
+    pub fn join(&self, client: Client<T, R>) -> BoxFuture<bool, ()> {
+        self.call(|reply| RoomCommand::Join(client, reply))
+    }
+
+    pub fn leave(&self, id: ClientId) -> BoxFuture<Option<Client<T, R>>, ()> {
+        self.call(|reply| RoomCommand::Leave(id, reply))
+    }
+
+    pub fn status(&self) -> BoxFuture<HashMap<ClientId, ClientStatus>, ()> {
+        self.call(RoomCommand::Status)
+    }
+
+    pub fn close(&self) -> BoxFuture<HashMap<ClientId, ClientStatus>, ()> {
+        self.call(RoomCommand::Close)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test::*;
+
+    // Every op in these tests resolves synchronously against a mock
+    // client, so once `commands_tx` is dropped (as every test below does
+    // right after queueing what it wants applied), polling `task` in a
+    // loop always drains the queue and then ends the task, exactly like
+    // `close_command_answers_and_ends_the_task` already does below.
+    fn drive<T, R>(task: &mut RoomTask<T, R>)
+        where T: Clone + Send + 'static,
+              R: Send + 'static
+    {
+        loop {
+            match task.poll().unwrap() {
+                Async::Ready(()) => break,
+                Async::NotReady => {}
+            }
+        }
+    }
+
+    #[test]
+    fn close_command_answers_and_ends_the_task() {
+        let (_rx, client) = mock_client_channelled();
+        let room = Room::new(vec![client]);
+
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Close(reply_tx)).unwrap();
+        drop(commands_tx);
+
+        // Drive the task's hand-rolled poll loop directly (no spawned
+        // reactor needed, since every op here resolves synchronously
+        // against the mock client) until it reports it's done.
+        loop {
+            match task.poll().unwrap() {
+                Async::Ready(()) => break,
+                Async::NotReady => {}
+            }
+        }
+
+        assert_eq!(reply_rx.wait().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn transmit_command_delivers_to_named_clients() {
+        let (rx, client) = mock_client_channelled();
+        let mut client_rx = rx.wait().peekable();
+        let id = client.id();
+
+        let room = Room::new(vec![client]);
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let mut msgs = HashMap::new();
+        msgs.insert(id, TinyMsg::A);
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Transmit(msgs, reply_tx)).unwrap();
+        drop(commands_tx);
+        drive(&mut task);
+
+        assert_eq!(reply_rx.wait().unwrap().get(&id), Some(&ClientStatus::Ready));
+        match client_rx.next() {
+            Some(Ok(_)) => {}
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn broadcast_command_reaches_every_client() {
+        let (rx0, client0) = mock_client_channelled();
+        let mut client0_rx = rx0.wait().peekable();
+        let (rx1, client1) = mock_client_channelled();
+        let mut client1_rx = rx1.wait().peekable();
+
+        let room = Room::new(vec![client0, client1]);
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Broadcast(TinyMsg::A, reply_tx)).unwrap();
+        drop(commands_tx);
+        drive(&mut task);
+
+        assert_eq!(reply_rx.wait().unwrap().len(), 2);
+        match (client0_rx.next(), client1_rx.next()) {
+            (Some(Ok(_)), Some(Ok(_))) => {}
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn status_command_reports_every_client() {
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let room = Room::new(vec![client]);
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Status(reply_tx)).unwrap();
+        drop(commands_tx);
+        drive(&mut task);
+
+        assert_eq!(reply_rx.wait().unwrap().get(&id), Some(&ClientStatus::Ready));
+    }
+
+    #[test]
+    fn receive_command_round_trips_through_the_task() {
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let room = Room::new(vec![client]);
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Receive(ClientTimeout::default(), reply_tx)).unwrap();
+        drop(commands_tx);
+        drive(&mut task);
+
+        // Just asserting the round trip reached the client: a mock
+        // client's inbound status after `receive` depends on how its
+        // fixture wires up the inbound channel, not on anything `RoomTask`
+        // controls.
+        let (statuses, _msgs) = reply_rx.wait().unwrap();
+        assert!(statuses.contains_key(&id));
+    }
+
+    #[test]
+    fn join_command_adds_a_client_then_status_shows_it() {
+        let room: Room<TinyMsg, TinyMsg> = Room::new(vec![]);
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let (join_tx, join_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Join(client, join_tx)).unwrap();
+        let (status_tx, status_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Status(status_tx)).unwrap();
+        drop(commands_tx);
+        drive(&mut task);
+
+        assert!(join_rx.wait().unwrap());
+        assert!(status_rx.wait().unwrap().contains_key(&id));
+    }
+
+    #[test]
+    fn leave_command_hands_back_the_client_and_removes_it() {
+        let (_rx, client) = mock_client_channelled();
+        let id = client.id();
+
+        let room = Room::new(vec![client]);
+        let (commands_tx, commands_rx) = mpsc::unbounded();
+        let mut task = RoomTask {
+            room: room,
+            commands: commands_rx,
+            in_flight: None,
+            closing: false,
+        };
+
+        let (leave_tx, leave_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Leave(id, leave_tx)).unwrap();
+        let (status_tx, status_rx) = oneshot::channel();
+        commands_tx.unbounded_send(RoomCommand::Status(status_tx)).unwrap();
+        drop(commands_tx);
+        drive(&mut task);
+
+        assert_eq!(leave_rx.wait().unwrap().map(|c| c.id()), Some(id));
+        assert!(!status_rx.wait().unwrap().contains_key(&id));
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use futures::{Future, Stream, BoxFuture, Poll, Async};
+use tokio_timer::Interval;
+use super::*;
+use super::broadcast::Broadcast;
+
+// Folds a round of `ClientStatus`es into the set of clients already known
+// to have failed a `start_send`/`poll_complete`, adding any that are closed.
+fn merge_evictions(mut failed: HashSet<ClientId>,
+                    statuses: HashMap<ClientId, ClientStatus>)
+                    -> HashSet<ClientId> {
+    for (id, status) in statuses {
+        if let ClientStatus::Closed = status {
+            failed.insert(id);
+        }
+    }
+    failed
+}
+
+fn tick<T, R>(room: Room<T, R>, ping_msg: T) -> BoxFuture<(Room<T, R>, HashSet<ClientId>), ()>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    let ids = room.client_ids().into_iter().collect();
+    Broadcast::new(room, ping_msg, ids)
+        .and_then(|(mut room, failed)| {
+            room.status().map(move |statuses| (room, merge_evictions(failed, statuses)))
+        })
+        .map(|(mut room, dead)| {
+            for id in &dead {
+                room.remove(id);
+            }
+            (room, dead)
+        })
+        .boxed()
+}
+
+// Periodically pings every client in a `Room` and evicts any whose
+// transport has died, modelled on `Broadcast` but driven by a `tokio_timer`
+// interval rather than resolving once.
+pub struct Keepalive<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    room: Option<Room<T, R>>,
+    interval: Interval,
+    ping_msg: T,
+    tick: Option<BoxFuture<(Room<T, R>, HashSet<ClientId>), ()>>,
+}
+
+impl<T, R> Keepalive<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    #[doc(hidden)]
+    pub fn new(room: Room<T, R>, interval: Duration, ping_msg: T) -> Keepalive<T, R> {
+        Keepalive {
+            room: Some(room),
+            interval: Interval::new_interval(interval),
+            ping_msg: ping_msg,
+            tick: None,
+        }
+    }
+}
+
+impl<T, R> Stream for Keepalive<T, R>
+    where T: Clone + Send + 'static,
+          R: Send + 'static
+{
+    // The set of clients evicted on this tick, which may be empty.
+    type Item = HashSet<ClientId>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(mut tick) = self.tick.take() {
+            return match tick.poll() {
+                Ok(Async::Ready((room, evicted))) => {
+                    self.room = Some(room);
+                    Ok(Async::Ready(Some(evicted)))
+                }
+                Ok(Async::NotReady) => {
+                    self.tick = Some(tick);
+                    Ok(Async::NotReady)
+                }
+                Err(()) => Err(()),
+            };
+        }
+
+        match self.interval.poll() {
+            Ok(Async::Ready(Some(_))) => {
+                let room = self.room.take().expect("Keepalive polled after its room was taken");
+                self.tick = Some(tick(room, self.ping_msg.clone()));
+                self.poll()
+            }
+            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test::*;
+
+    #[test]
+    fn merge_evictions_adds_closed_clients_to_already_failed() {
+        let (_rx0, client0) = mock_client_channelled();
+        let (_rx1, client1) = mock_client_channelled();
+
+        let mut already_failed = HashSet::new();
+        already_failed.insert(client0.id());
+
+        let mut statuses = HashMap::new();
+        statuses.insert(client1.id(), ClientStatus::Closed);
+
+        let dead = merge_evictions(already_failed, statuses);
+
+        assert!(dead.contains(&client0.id()));
+        assert!(dead.contains(&client1.id()));
+    }
+}